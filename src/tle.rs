@@ -1,6 +1,9 @@
 extern crate core;
 
+use std::collections::HashMap;
 use std::convert::From;
+use std::io;
+use std::io::Read;
 use std::num::{ParseIntError, ParseFloatError};
 
 #[derive(Clone, PartialEq, Debug)]
@@ -8,43 +11,61 @@ pub struct TLE {
     // line 1
     name: String,
 
-    satellite_number: i16,
+    satellite_number: u32,
     classification: Classification,
     id_launch_year: i8,
     id_launch_number: i16,
     id_launch_piece: String,
     epoch_year: i16,
-    epoch: f32,
-    mean_motion_d: f32,
-    mean_motion_dd: f32,
-    bstar: f32,
+    epoch: f64,
+    mean_motion_d: f64,
+    mean_motion_dd: f64,
+    bstar: f64,
     set_number: i32,
 
     // line 2
-    inclination: f32,
-    right_ascension: f32,
-    eccentricity: f32,
-    perigree: f32,
-    mean_anomaly: f32,
-    mean_motion: f32,
+    inclination: f64,
+    right_ascension: f64,
+    eccentricity: f64,
+    perigree: f64,
+    mean_anomaly: f64,
+    mean_motion: f64,
     revolution_number: i32
 }
 
 #[derive(Clone,PartialEq,Debug)]
 pub enum Classification {
     Unclassified,
+    Classified,
+    Secret,
     Other
 }
 
 #[derive(Debug)]
 pub enum DeserializationError {
     ParseError(String),
+    ChecksumMismatch { line: u8 },
+    Io(String),
 }
 
 impl TLE {
-    #[allow(dead_code)]
-    fn validate() -> bool {
-        panic!("Fix me. We need access to the raw line data.");
+    // periods of 225 minutes and more need the deep space (SDP4) treatment
+    pub fn is_deep_space(&self) -> bool {
+        1440.0 / self.mean_motion >= 225.0
+    }
+
+    pub fn is_retrograde(&self) -> bool {
+        self.inclination > 90.0
+    }
+
+    pub fn is_newer_than(&self, other: &TLE) -> bool {
+        (self.epoch_year, self.epoch) > (other.epoch_year, other.epoch)
+    }
+
+    // mean motion in rev/day after `days` since epoch, applying the first
+    // and second time derivatives
+    pub fn mean_motion_at(&self, days: f64) -> f64 {
+        self.mean_motion + self.mean_motion_d * days + self.mean_motion_dd / 2.0 * days * days
     }
 }
 
@@ -56,28 +77,154 @@ impl From<ParseIntError> for DeserializationError {
     fn from(err: ParseIntError) -> DeserializationError {DeserializationError::ParseError(err.description().to_string())}
 }
 
+impl From<io::Error> for DeserializationError {
+    fn from(err: io::Error) -> DeserializationError {DeserializationError::Io(err.to_string())}
+}
+
+fn line_checksum(line: &str) -> u32 {
+    line.chars().take(68).fold(0, |sum, c| match c {
+        '-' => sum + 1,
+        _ => sum + c.to_digit(10).unwrap_or(0)
+    }) % 10
+}
+
+// checks the line number prefix and the trailing checksum digit
+fn check_line(line: &str, number: u8) -> Result<(), DeserializationError> {
+    let malformed = DeserializationError::ParseError(format!("malformed TLE line {}", number));
+
+    if line.len() < 69 || !line.starts_with(&format!("{} ", number)) {
+        return Err(malformed);
+    }
+
+    match (line.as_bytes()[68] as char).to_digit(10) {
+        Some(checksum) if checksum == line_checksum(line) => Ok(()),
+        Some(_) => Err(DeserializationError::ChecksumMismatch { line: number }),
+        None => Err(malformed)
+    }
+}
+
+// alpha-5 catalog numbers replace the leading digit with a letter,
+// A = 10 .. Z = 33 skipping I and O: "E8493" is 148493
+fn decode_satellite_number(s: &str) -> Result<u32, DeserializationError> {
+    let s = s.trim();
+    match s.as_bytes().first() {
+        Some(&c) if c >= b'A' && c <= b'Z' && c != b'I' && c != b'O' => {
+            let mut prefix = (c - b'A') as u32 + 10;
+            if c > b'I' { prefix -= 1; }
+            if c > b'O' { prefix -= 1; }
+            Ok(prefix * 10000 + try!(s[1..].parse::<u32>()))
+        },
+        _ => Ok(try!(s.parse::<u32>()))
+    }
+}
+
+fn encode_satellite_number(number: u32) -> String {
+    let prefix = number / 10000;
+    if number < 100000 || prefix > 33 { return format!("{:05}", number) };
+
+    let mut c = b'A' + (prefix - 10) as u8;
+    if c >= b'I' { c += 1; }
+    if c >= b'O' { c += 1; }
+    format!("{}{:04}", c as char, number % 10000)
+}
+
+// two digit epoch years 57-99 are 1957-1999, 00-56 are 2000-2056
+fn epoch_year(year: i16) -> i16 {
+    if year < 57 { 2000 + year } else { 1900 + year }
+}
+
+// turns the implied decimal point notation into something parse::<f64>
+// understands: "-31134-2" is -0.31134e-2, "7228326" is 0.7228326 and
+// "-.00000207" is -0.00000207
 fn fix_string(s: String) -> String {
-    if s.starts_with("-.") { return s.replace("-.", "-0.") };
+    let (sign, digits) = match s.chars().next() {
+        Some('-') => ("-", &s[1..]),
+        Some('+') => ("", &s[1..]),
+        _ => ("", &s[..])
+    };
+
+    if digits.starts_with(".") { return format!("{}0{}", sign, digits) };
+
+    let len = digits.len();
+    if len > 2 && (digits[(len - 2)..].starts_with("-") || digits[(len - 2)..].starts_with("+")) {
+        format!("{}0.{}e{}", sign, &digits[..(len - 2)], &digits[(len - 2)..])
+    } else {
+        format!("{}0.{}", sign, digits)
+    }
+}
+
+// inverse of fix_string: 8 columns of sign, five digit mantissa and exponent
+fn unfix_float(v: f64) -> String {
+    if v == 0.0 { return " 00000-0".to_string() };
 
-    match s.find("-") {
-        Some(0) => return "-0.".to_string() + &s[1..(s.len() - 2)].to_string() + "e" + &s[(s.len() - 2)..s.len()],
-        Some(_) => return "0.".to_string() + &s[0..(s.len() - 2)].to_string() + "e" + &s[(s.len() - 2)..s.len()],
-        None => return format!("0.{}", s)
+    let mut exponent = v.abs().log10().floor() as i32 + 1;
+    let mut mantissa = (v.abs() / 10f64.powi(exponent) * 1e5).round() as u32;
+    if mantissa >= 100000 {
+        mantissa /= 10;
+        exponent += 1;
     }
+
+    format!("{}{:05}{}{}",
+            if v < 0.0 { "-" } else { " " },
+            mantissa,
+            if exponent > 0 { "+" } else { "-" },
+            exponent.abs())
+}
+
+// "-.00000207" / " .00012260": sign column followed by an implied leading zero
+fn unfix_decimal(v: f64) -> String {
+    let s = format!("{:.8}", v.abs());
+    format!("{}{}", if v < 0.0 { "-" } else { " " }, &s[1..])
 }
 
 pub fn deserialize_tle(input: String) -> Result<TLE, DeserializationError> {
-    //TODO can we somehow enforce the parameter to have a fixed length?
-    let lines : Vec<&str> = input.lines().collect();
-    let name = &lines[0];
-    let line1 = &lines[1];
-    let line2 = &lines[2];
+    deserialize(input, false)
+}
+
+// like deserialize_tle, but fails on checksum mismatches
+pub fn deserialize_tle_strict(input: String) -> Result<TLE, DeserializationError> {
+    deserialize(input, true)
+}
+
+fn deserialize(input: String, strict: bool) -> Result<TLE, DeserializationError> {
+    // a stray '\r' would shift the fixed columns
+    let lines : Vec<&str> = input.lines().map(|l| l.trim_right_matches('\r')).collect();
+    // raw 2-line element sets come without the name line
+    let (name, data) = match lines.first() {
+        Some(first) if first.starts_with("2 ") => {
+            return Err(DeserializationError::ParseError("TLE starts with line 2".to_string()))
+        },
+        Some(first) if first.starts_with("1 ") && first.len() >= 69 => (None, &lines[..]),
+        Some(first) => (Some(*first), &lines[1..]),
+        None => return Err(DeserializationError::ParseError("empty TLE".to_string()))
+    };
+
+    // everything below slices fixed columns, which only works for ASCII
+    if data.len() < 2 || data[0].len() < 69 || data[1].len() < 69 {
+        return Err(DeserializationError::ParseError("TLE lines must be 69 characters long".to_string()));
+    }
+    if !data[0].is_ascii() || !data[1].is_ascii() {
+        return Err(DeserializationError::ParseError("TLE lines must be ASCII".to_string()));
+    }
+    let (line1, line2) = (data[0], data[1]);
+
+    if strict {
+        try!(check_line(line1, 1));
+        try!(check_line(line2, 2));
+    }
+
+    let satellite_number = try!(decode_satellite_number(&line1[2..7]));
 
     let tle = TLE {
-        name: name.to_string(),
-        satellite_number: try!(line1[2..7].parse::<i16>()),
-        classification: match line1.as_bytes()[8] {
+        name: match name {
+            Some(name) => name.trim().to_string(),
+            None => format!("NORAD {}", satellite_number)
+        },
+        satellite_number: satellite_number,
+        classification: match line1.as_bytes()[7] {
             b'U' => Classification::Unclassified,
+            b'C' => Classification::Classified,
+            b'S' => Classification::Secret,
             _ => Classification::Other
         },
 
@@ -86,30 +233,158 @@ pub fn deserialize_tle(input: String) -> Result<TLE, DeserializationError> {
         id_launch_piece: line1[14..17].trim().to_string(),
 
 
-        epoch_year: try!(line1[18..20].parse::<i16>()),
-        epoch: try!(line1[20..32].trim().parse::<f32>()),
-        mean_motion_d: try!(fix_string(line1[33..43].to_string()).parse::<f32>().map(|v| v * 2.0)),
-        mean_motion_dd: try!(fix_string(line1[44..52].trim().to_string()).parse::<f32>().map(|v| v * 6.0)),
-        bstar: try!(fix_string(line1[53..61].trim().to_string()).parse::<f32>()),
+        epoch_year: epoch_year(try!(line1[18..20].parse::<i16>())),
+        epoch: try!(line1[20..32].trim().parse::<f64>()),
+        mean_motion_d: try!(line1[33..43].trim().parse::<f64>().map(|v| v * 2.0)),
+        mean_motion_dd: try!(fix_string(line1[44..52].trim().to_string()).parse::<f64>().map(|v| v * 6.0)),
+        bstar: try!(fix_string(line1[53..61].trim().to_string()).parse::<f64>()),
         set_number: try!(line1[64..68].trim().parse::<i32>()),
 
         // line 2
-        inclination: try!(line2[08..16].trim().parse::<f32>()),
-        right_ascension: try!(line2[17..25].trim().parse::<f32>()),
-        eccentricity: try!(fix_string(line2[26..33].trim().to_string()).parse::<f32>()),
-        perigree: try!(line2[34..42].trim().parse::<f32>()),
-        mean_anomaly: try!(line2[43..51].trim().parse::<f32>()),
-        mean_motion: try!(line2[52..63].trim().parse::<f32>()),
-        revolution_number: try!(line2[63..68].parse::<i32>())
+        inclination: try!(line2[08..16].trim().parse::<f64>()),
+        right_ascension: try!(line2[17..25].trim().parse::<f64>()),
+        eccentricity: try!(fix_string(line2[26..33].trim().to_string()).parse::<f64>()),
+        perigree: try!(line2[34..42].trim().parse::<f64>()),
+        mean_anomaly: try!(line2[43..51].trim().parse::<f64>()),
+        mean_motion: try!(line2[52..63].trim().parse::<f64>()),
+        revolution_number: try!(line2[63..68].trim().parse::<i32>())
     };
 
+    // only closed orbits make sense here
+    if tle.eccentricity < 0.0 || tle.eccentricity >= 1.0 {
+        return Err(DeserializationError::ParseError(format!("eccentricity {} out of range", tle.eccentricity)));
+    }
+
     Ok(tle)
 }
 
-//FIXME is there some "not implemented" annotation we could use instead?"
-#[allow(dead_code, unused_variables)]
-pub fn serialize_tle(tle: TLE) -> String {
-    panic!("Not implemented.");
+pub fn serialize_tle(tle: &TLE) -> String {
+    let line1 = format!("1 {}{} {:02}{:03}{:<3} {:02}{:012.8} {} {} {} 0 {:>4}",
+                        encode_satellite_number(tle.satellite_number),
+                        match tle.classification {
+                            Classification::Unclassified => 'U',
+                            Classification::Classified => 'C',
+                            Classification::Secret => 'S',
+                            Classification::Other => ' '
+                        },
+                        tle.id_launch_year,
+                        tle.id_launch_number,
+                        tle.id_launch_piece,
+                        tle.epoch_year % 100,
+                        tle.epoch,
+                        unfix_decimal(tle.mean_motion_d / 2.0),
+                        unfix_float(tle.mean_motion_dd / 6.0),
+                        unfix_float(tle.bstar),
+                        tle.set_number);
+
+    let line2 = format!("2 {} {:8.4} {:8.4} {:07} {:8.4} {:8.4} {:11.8}{:>5}",
+                        encode_satellite_number(tle.satellite_number),
+                        tle.inclination,
+                        tle.right_ascension,
+                        (tle.eccentricity * 1e7).round() as u32,
+                        tle.perigree,
+                        tle.mean_anomaly,
+                        tle.mean_motion,
+                        tle.revolution_number);
+
+    format!("{}\n{}{}\n{}{}",
+            tle.name,
+            line1, line_checksum(&line1),
+            line2, line_checksum(&line2))
+}
+
+// anything readable works, e.g. std::io::stdin()
+pub fn parse_file<R: Read>(file: &mut R) -> Result<Vec<TLE>, DeserializationError> {
+    let mut content = String::new();
+    try!(file.read_to_string(&mut content));
+
+    Ok(parse_str(&content))
+}
+
+// like parse_file, but also returns the input line and error of every failed entry
+pub fn parse_file_verbose<R: Read>(file: &mut R, strict: bool) -> Result<(Vec<TLE>, Vec<(usize, DeserializationError)>), DeserializationError> {
+    let mut content = String::new();
+    try!(file.read_to_string(&mut content));
+
+    Ok(parse_str_verbose(&content, strict))
+}
+
+pub fn parse_str(s: &str) -> Vec<TLE> {
+    parse(s, false).0
+}
+
+pub fn parse_str_verbose(s: &str, strict: bool) -> (Vec<TLE>, Vec<(usize, DeserializationError)>) {
+    parse(s, strict)
+}
+
+fn parse(s: &str, strict: bool) -> (Vec<TLE>, Vec<(usize, DeserializationError)>) {
+    // blank lines and '#' comments aren't part of any entry, but errors
+    // refer to line numbers in the original input
+    let lines : Vec<(usize, &str)> = s.lines()
+        .enumerate()
+        .filter(|&(_, l)| !l.trim().is_empty() && !l.starts_with("#"))
+        .collect();
+    let mut tles = Vec::new();
+    let mut errors = Vec::new();
+
+    // an entry is an optional name line followed by lines "1 ..." and "2 ...";
+    // an orphaned element line is never taken for a name
+    let mut i = 0;
+    let mut in_junk = false;
+    while i < lines.len() {
+        let start = i;
+        if !lines[i].1.starts_with("1 ") && !lines[i].1.starts_with("2 ") { i += 1; }
+
+        if i + 1 < lines.len() && lines[i].1.starts_with("1 ") && lines[i + 1].1.starts_with("2 ") {
+            let entry : Vec<&str> = lines[start..(i + 2)].iter().map(|&(_, l)| l).collect();
+            match deserialize(entry.join("\n"), strict) {
+                Ok(tle) => tles.push(tle),
+                Err(err) => errors.push((lines[start].0, err))
+            }
+            i += 2;
+            in_junk = false;
+        } else {
+            // a run of lines that don't form an entry is reported once
+            if !in_junk {
+                errors.push((lines[start].0, DeserializationError::ParseError("incomplete TLE".to_string())));
+            }
+            i = start + 1;
+            in_junk = true;
+        }
+    }
+
+    (tles, errors)
+}
+
+// catalogs concatenated from several downloads carry multiple element
+// sets per satellite; pick the one with the most recent epoch
+pub fn latest_for(tles: &[TLE], number: u32) -> Option<&TLE> {
+    tles.iter()
+        .filter(|t| t.satellite_number == number)
+        .fold(None, |latest, t| match latest {
+            Some(l) if !t.is_newer_than(l) => Some(l),
+            _ => Some(t)
+        })
+}
+
+// collapses a catalog to the most recent element set per satellite,
+// keeping the order in which satellites first appear
+pub fn dedup_latest(tles: Vec<TLE>) -> Vec<TLE> {
+    let mut index = HashMap::new();
+    let mut latest : Vec<TLE> = Vec::new();
+
+    for tle in tles {
+        let existing = index.get(&tle.satellite_number).cloned();
+        match existing {
+            Some(i) => if tle.is_newer_than(&latest[i]) { latest[i] = tle },
+            None => {
+                index.insert(tle.satellite_number, latest.len());
+                latest.push(tle);
+            }
+        }
+    }
+
+    latest
 }
 
 #[cfg(test)]
@@ -119,9 +394,395 @@ mod test {
         1 21426U 91043A   15108.55037587 -.00000207  00000-0 -31134-2 0  9992\n\
         2 21426  63.2890 290.2925 7228326 283.8438  15.2252  2.00627254174658";
 
+    pub const ISS_DATA : &'static str =
+        "ISS (ZARYA)\n\
+        1 25544U 98067A   08264.51782528 -.00002182  00000-0 -11606-4 0  2927\n\
+        2 25544  51.6416 247.4627 0006703 130.5360 325.0288 15.72125391563537";
+
     #[test]
     fn test_deserialize_tle() {
         let t = super::deserialize_tle(DATA.to_string());
         assert!(t.is_ok());
     }
+
+    #[test]
+    fn test_classification() {
+        use super::Classification;
+
+        let t = super::deserialize_tle(DATA.replace("21426U", "21426C")).unwrap();
+        assert_eq!(t.classification, Classification::Classified);
+        assert!(super::serialize_tle(&t).contains("1 21426C "));
+
+        let t = super::deserialize_tle(DATA.replace("21426U", "21426S")).unwrap();
+        assert_eq!(t.classification, Classification::Secret);
+
+        let t = super::deserialize_tle(DATA.replace("21426U", "21426X")).unwrap();
+        assert_eq!(t.classification, Classification::Other);
+        assert_eq!(super::deserialize_tle(super::serialize_tle(&t)).unwrap(), t);
+    }
+
+    #[test]
+    fn test_deserialize_two_line_tle() {
+        let three = super::deserialize_tle(ISS_DATA.to_string()).unwrap();
+        let two_line_data = ISS_DATA.lines().skip(1).collect::<Vec<_>>().join("\n");
+        let mut two = super::deserialize_tle(two_line_data).unwrap();
+
+        assert_eq!(two.name, "NORAD 25544");
+        two.name = three.name.clone();
+        assert_eq!(two, three);
+    }
+
+    #[test]
+    fn test_deserialize_strict() {
+        assert!(super::deserialize_tle_strict(DATA.to_string()).is_ok());
+
+        let broken = DATA.replace("174658", "174659");
+        assert!(super::deserialize_tle(broken.clone()).is_ok());
+        match super::deserialize_tle_strict(broken) {
+            Err(super::DeserializationError::ChecksumMismatch { line: 2 }) => (),
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_deserialize_bad_eccentricity() {
+        match super::deserialize_tle(DATA.replace("7228326", "-123456")) {
+            Err(super::DeserializationError::ParseError(_)) => (),
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_deserialize_padded_name() {
+        let data = DATA.replace("MOLNIYA 1-81", "MOLNIYA 1-81            ");
+        assert_eq!(data.lines().next().unwrap().len(), 24);
+        assert_eq!(super::deserialize_tle(data).unwrap().name, "MOLNIYA 1-81");
+    }
+
+    #[test]
+    fn test_parse_str_blank_and_comment_lines() {
+        let data = format!("# weather and misc\n{}\n\n\r\n{}\n\n", DATA, ISS_DATA);
+        let (tles, errors) = super::parse_str_verbose(&data, false);
+        assert_eq!(errors.len(), 0);
+        assert_eq!(tles.len(), 2);
+        assert_eq!(tles[1].name, "ISS (ZARYA)");
+    }
+
+    #[test]
+    fn test_parse_str_mixed_formats() {
+        let two_line = ISS_DATA.lines().skip(1).collect::<Vec<_>>().join("\n");
+        let data = format!("{}\n{}\n{}", two_line, DATA, two_line);
+        let (tles, errors) = super::parse_str_verbose(&data, false);
+        assert_eq!(errors.len(), 0);
+        assert_eq!(tles.iter().map(|t| &t.name[..]).collect::<Vec<_>>(),
+                   vec!["NORAD 25544", "MOLNIYA 1-81", "NORAD 25544"]);
+
+        // a dangling name line doesn't take the following entry down with it
+        let data = format!("LOST\n{}", DATA);
+        let (tles, errors) = super::parse_str_verbose(&data, false);
+        assert_eq!(tles.len(), 1);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, 0);
+
+        // a run of junk is one error, and line numbers count skipped lines
+        let data = format!("{}\n\n# junk follows\nX\n2 bad\nY\n{}", DATA, DATA.replace("174658", "174659"));
+        let (tles, errors) = super::parse_str_verbose(&data, true);
+        assert_eq!(tles.len(), 1);
+        assert_eq!(errors.iter().map(|e| e.0).collect::<Vec<_>>(), vec![5, 8]);
+
+        // 2-line catalog where the middle entry lost its line 1
+        let iss : Vec<&str> = ISS_DATA.lines().skip(1).collect();
+        let molniya : Vec<&str> = DATA.lines().skip(1).collect();
+        let data = [iss[0], iss[1], molniya[1], molniya[0], molniya[1]].join("\n");
+        let (tles, errors) = super::parse_str_verbose(&data, false);
+        assert_eq!(tles.iter().map(|t| &t.name[..]).collect::<Vec<_>>(),
+                   vec!["NORAD 25544", "NORAD 21426"]);
+        assert_eq!(errors.iter().map(|e| e.0).collect::<Vec<_>>(), vec![2]);
+
+        assert!(super::deserialize_tle([molniya[1], molniya[0], molniya[1]].join("\n")).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_crlf() {
+        let t = super::deserialize_tle(DATA.to_string()).unwrap();
+        assert_eq!(super::deserialize_tle(DATA.replace("\n", "\r\n")).unwrap(), t);
+        assert_eq!(super::deserialize_tle(DATA.replace("\n", "\r\r\n")).unwrap(), t);
+        assert_eq!(super::parse_str(&DATA.replace("\n", "\r\n")), vec![t]);
+    }
+
+    #[test]
+    fn test_deserialize_short_lines() {
+        assert!(super::deserialize_tle("".to_string()).is_err());
+        assert!(super::deserialize_tle("X\n1\n2".to_string()).is_err());
+
+        let truncated = DATA.lines().map(|l| &l[..l.len().min(20)]).collect::<Vec<_>>().join("\n");
+        assert!(super::deserialize_tle(truncated).is_err());
+
+        // same byte length would still split a char at a column boundary
+        let non_ascii = DATA.replace("15108.55", "1é108.5");
+        assert!(super::deserialize_tle(non_ascii.clone()).is_err());
+        assert_eq!(super::parse_str_verbose(&non_ascii, false).1.len(), 1);
+    }
+
+    #[test]
+    fn test_satellite_number() {
+        assert_eq!(super::decode_satellite_number("21426").unwrap(), 21426);
+        assert_eq!(super::decode_satellite_number("00005").unwrap(), 5);
+        assert_eq!(super::decode_satellite_number("A0000").unwrap(), 100000);
+        assert_eq!(super::decode_satellite_number("E8493").unwrap(), 148493);
+        assert_eq!(super::decode_satellite_number("J2931").unwrap(), 182931);
+        assert_eq!(super::decode_satellite_number("Z9999").unwrap(), 339999);
+        assert!(super::decode_satellite_number("I0000").is_err());
+
+        for &n in [5, 21426, 99999, 100000, 148493, 182931, 339999].iter() {
+            let encoded = super::encode_satellite_number(n);
+            assert_eq!(encoded.len(), 5);
+            assert_eq!(super::decode_satellite_number(&encoded).unwrap(), n);
+        }
+
+        let t = super::deserialize_tle(DATA.replace("21426", "E8493")).unwrap();
+        assert_eq!(t.satellite_number, 148493);
+        assert!(super::serialize_tle(&t).contains("1 E8493U"));
+    }
+
+    #[test]
+    fn test_high_satellite_number() {
+        let data = DATA.replace("21426", "43013");
+        let t = super::deserialize_tle(data.clone()).unwrap();
+        assert_eq!(t.satellite_number, 43013);
+        assert_eq!(super::serialize_tle(&t)[..20], data[..20]);
+    }
+
+    #[test]
+    fn test_epoch_year() {
+        assert_eq!(super::epoch_year(98), 1998);
+        assert_eq!(super::epoch_year(57), 1957);
+        assert_eq!(super::epoch_year(0), 2000);
+        assert_eq!(super::epoch_year(56), 2056);
+
+        assert_eq!(super::deserialize_tle(DATA.to_string()).unwrap().epoch_year, 2015);
+        assert_eq!(super::deserialize_tle(ISS_DATA.to_string()).unwrap().epoch_year, 2008);
+    }
+
+    #[test]
+    fn test_is_deep_space() {
+        assert!(super::deserialize_tle(DATA.to_string()).unwrap().is_deep_space());
+        assert!(!super::deserialize_tle(ISS_DATA.to_string()).unwrap().is_deep_space());
+    }
+
+    #[test]
+    fn test_is_retrograde() {
+        assert!(!super::deserialize_tle(ISS_DATA.to_string()).unwrap().is_retrograde());
+
+        // sun-synchronous orbits are slightly retrograde
+        let sso = ISS_DATA.replace(" 51.6416", " 98.7100");
+        assert!(super::deserialize_tle(sso).unwrap().is_retrograde());
+    }
+
+    #[test]
+    fn test_mean_motion_at() {
+        // ndot/2 = -.00002182 and nddot/6 = 12345-5 as written in the TLE
+        let t = super::deserialize_tle(ISS_DATA.replace(" 00000-0 -11606-4", " 12345-5 -11606-4")).unwrap();
+        let n = 15.72125391;
+        let nd = 2.0 * -0.00002182;
+        let ndd = 6.0 * 0.12345e-5;
+
+        assert_eq!(t.mean_motion_at(0.0), n);
+        assert!((t.mean_motion_at(2.0) - (n + 2.0 * nd + 2.0 * ndd)).abs() < 1e-12);
+        assert!((t.mean_motion_at(-3.0) - (n - 3.0 * nd + 4.5 * ndd)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_latest_for() {
+        let newer = ISS_DATA.replace("08264.51782528", "08265.12345678");
+        let tles = super::parse_str(&format!("{}\n{}\n{}", ISS_DATA, newer, DATA));
+        let reversed = tles.iter().rev().cloned().collect::<Vec<_>>();
+
+        for tles in [tles, reversed].iter() {
+            assert_eq!(super::latest_for(tles, 25544).unwrap().epoch, 265.12345678);
+            assert_eq!(super::latest_for(tles, 21426).unwrap().name, "MOLNIYA 1-81");
+            assert!(super::latest_for(tles, 1).is_none());
+        }
+    }
+
+    #[test]
+    fn test_dedup_latest() {
+        let older = ISS_DATA.replace("08264.51782528", "08263.51782528");
+        let newest = ISS_DATA.replace("08264.51782528", "08265.51782528");
+        let tles = super::parse_str(&format!("{}\n{}\n{}\n{}", ISS_DATA, DATA, newest, older));
+        assert_eq!(tles.len(), 4);
+
+        let tles = super::dedup_latest(tles);
+        assert_eq!(tles.len(), 2);
+        assert_eq!(tles[0].satellite_number, 25544);
+        assert_eq!(tles[0].epoch, 265.51782528);
+        assert_eq!(tles[1].satellite_number, 21426);
+    }
+
+    #[test]
+    fn test_serialize_tle() {
+        let t = super::deserialize_tle(DATA.to_string()).unwrap();
+        let s = super::serialize_tle(&t);
+        assert_eq!(s, DATA.trim());
+        assert_eq!(super::deserialize_tle(s).unwrap(), t);
+    }
+
+    fn with_temp_file<F: Fn(&mut ::std::fs::File)>(name: &str, data: &str, f: F) {
+        use std::fs::{self, File};
+        use std::io::Write;
+
+        let path = ::std::env::temp_dir().join(name);
+        File::create(&path).unwrap().write_all(data.as_bytes()).unwrap();
+        f(&mut File::open(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_parse_file() {
+        with_temp_file("orbit_test_parse_file.tle", DATA, |file| {
+            let tles = super::parse_file(file).unwrap();
+            assert_eq!(tles.len(), 1);
+        });
+    }
+
+    #[test]
+    fn test_parse_file_io_error() {
+        // not UTF-8, so read_to_string fails
+        match super::parse_file(&mut &b"\xff\xfe"[..]) {
+            Err(super::DeserializationError::Io(_)) => (),
+            other => panic!("unexpected result {:?}", other)
+        }
+
+        for &strict in [false, true].iter() {
+            match super::parse_file_verbose(&mut &b"\xff\xfe"[..], strict) {
+                Err(super::DeserializationError::Io(_)) => (),
+                other => panic!("unexpected result {:?}", other)
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_file_verbose() {
+        let data = DATA.to_string() + "\n" + &DATA.replace("174658", "174659");
+        let (tles, errors) = super::parse_file_verbose(&mut data.as_bytes(), true).unwrap();
+        assert_eq!(tles.len(), 1);
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_reader() {
+        let tles = super::parse_file(&mut DATA.as_bytes()).unwrap();
+        assert_eq!(tles.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_str_verbose() {
+        // last checksum digit of the second entry is off by one
+        let data = DATA.to_string() + "\n" + &DATA.replace("174658", "174659");
+        let (tles, errors) = super::parse_str_verbose(&data, true);
+        assert_eq!(tles.len(), 1);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, 3);
+        match errors[0].1 {
+            super::DeserializationError::ChecksumMismatch { line: 2 } => (),
+            ref err => panic!("unexpected error {:?}", err)
+        }
+
+        // lenient, it yields the same TLEs as parse_str
+        let (tles, errors) = super::parse_str_verbose(&data, false);
+        assert_eq!(tles, super::parse_str(&data));
+        assert_eq!(tles.len(), 2);
+        assert_eq!(errors.len(), 0);
+    }
+
+    #[test]
+    fn test_parse_str() {
+        let tles = super::parse_str(DATA);
+        assert_eq!(tles.len(), 1);
+        assert_eq!(tles[0].name, "MOLNIYA 1-81");
+    }
+
+    fn fixed(s: &str) -> f64 {
+        super::fix_string(s.to_string()).parse::<f64>().unwrap()
+    }
+
+    #[test]
+    fn test_fix_string() {
+        assert_eq!(fixed("-.00000207"), -0.00000207);
+        assert_eq!(fixed(".00012260"), 0.00012260);
+        assert_eq!(fixed("86027-4"), 0.86027e-4);
+        assert_eq!(fixed("-31134-2"), -0.31134e-2);
+        assert_eq!(fixed("-11606-4"), -0.11606e-4);
+        assert_eq!(fixed("12345+1"), 1.2345);
+        assert_eq!(fixed("-12345+1"), -1.2345);
+        assert_eq!(fixed("+12345-1"), 0.012345);
+        assert_eq!(fixed("7228326"), 0.7228326);
+        assert_eq!(fixed("0006703"), 0.0006703);
+    }
+
+    #[test]
+    fn test_fix_string_zero() {
+        assert_eq!(super::fix_string("00000-0".to_string()), "0.00000e-0");
+        assert_eq!(fixed("00000-0"), 0.0);
+        assert_eq!(fixed("00000+0"), 0.0);
+        assert_eq!(super::unfix_float(0.0), " 00000-0");
+
+        for data in [DATA, ISS_DATA].iter() {
+            assert_eq!(super::deserialize_tle(data.to_string()).unwrap().mean_motion_dd, 0.0);
+        }
+    }
+
+    #[test]
+    fn test_fix_string_round_trip() {
+        for &sign in [1.0, -1.0].iter() {
+            for &mantissa in [0.1, 0.12345, 0.31134, 0.5, 0.86027, 0.99999].iter() {
+                for exponent in -9..3 {
+                    let v = sign * mantissa * 10f64.powi(exponent);
+
+                    let encoded = super::unfix_float(v);
+                    assert_eq!(encoded.len(), 8);
+                    let decoded = fixed(encoded.trim());
+                    assert!(((decoded - v) / v).abs() < 1e-9, "{} -> {} -> {}", v, encoded, decoded);
+                }
+
+                let v = sign * mantissa / 100.0;
+                let encoded = super::unfix_decimal(v);
+                assert_eq!(encoded.len(), 10);
+                assert!((fixed(encoded.trim()) - v).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_line_checksum() {
+        for line in DATA.lines().skip(1) {
+            let expected = line[68..69].parse::<u32>().unwrap();
+            assert_eq!(super::line_checksum(line), expected);
+        }
+    }
+
+    #[test]
+    fn test_check_line() {
+        use super::DeserializationError::{ChecksumMismatch, ParseError};
+
+        let lines : Vec<&str> = DATA.lines().collect();
+        assert!(super::check_line(lines[1], 1).is_ok());
+        assert!(super::check_line(lines[2], 2).is_ok());
+
+        let wrong_checksum = lines[1][..68].to_string() + "3";
+        match super::check_line(&wrong_checksum, 1) {
+            Err(ChecksumMismatch { line: 1 }) => (),
+            other => panic!("unexpected result {:?}", other)
+        }
+
+        match super::check_line(lines[1], 2) {
+            Err(ParseError(_)) => (),
+            other => panic!("unexpected result {:?}", other)
+        }
+
+        match super::check_line(&lines[1][..40], 1) {
+            Err(ParseError(_)) => (),
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
 }